@@ -1,16 +1,21 @@
 extern crate cc;
+extern crate semver;
 
 use std::env;
 use std::fs::{self, File};
-use std::io::Write;
+use std::io::{self, Read, Write};
 use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const ENV_NAME: &str = "NATIVE_VERSIONING_VERSION";
 
 /// Error enum.
 #[derive(Debug)]
 pub enum Error {
     Io(::std::io::Error),
     EnvVar(::std::env::VarError),
-    Fmt(::std::fmt::Error)
+    Fmt(::std::fmt::Error),
+    Semver(::semver::Error),
 }
 
 impl From<::std::io::Error> for Error {
@@ -31,15 +36,351 @@ impl From<::std::fmt::Error> for Error {
     }
 }
 
-#[macro_export]
-fn version() -> Result<String, Error> {
+impl From<::semver::Error> for Error {
+    fn from(error: ::semver::Error) -> Self {
+        Error::Semver(error)
+    }
+}
+
+/// Controls how a crate's `CARGO_PKG_VERSION` is mangled into a legal C
+/// identifier suffix.
+///
+/// The major, minor, and patch components are always included. By default,
+/// the prerelease component (`beta.1` in `1.2.3-beta.1`) is included and
+/// build metadata (`build.3` in `1.2.3+build.3`) is not, since semver
+/// considers build metadata irrelevant to version precedence. Either can be
+/// toggled with [`include_pre`] and [`include_build`]. Whatever is included
+/// is sanitized into a legal C identifier: every character outside
+/// `[A-Za-z0-9_]` is replaced with `_`, and the result is guaranteed not to
+/// begin with a digit.
+///
+/// [`include_pre`]: VersionScheme::include_pre
+/// [`include_build`]: VersionScheme::include_build
+#[derive(Debug, Clone)]
+pub struct VersionScheme {
+    include_pre: bool,
+    include_build: bool,
+    include_dirty: bool,
+}
+
+impl Default for VersionScheme {
+    fn default() -> Self {
+        VersionScheme { include_pre: true, include_build: false, include_dirty: false }
+    }
+}
+
+impl VersionScheme {
+    /// Creates a scheme with the default settings: prerelease included,
+    /// build metadata omitted, dirty marker omitted.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether the prerelease component is included in the mangled
+    /// version. Enabled by default.
+    pub fn include_pre(mut self, include: bool) -> Self {
+        self.include_pre = include;
+        self
+    }
+
+    /// Sets whether build metadata is included in the mangled version.
+    /// Disabled by default.
+    pub fn include_build(mut self, include: bool) -> Self {
+        self.include_build = include;
+        self
+    }
+
+    /// Sets whether a trailing `_dirty` marker is appended to the mangled
+    /// version when the working tree has uncommitted changes. Disabled by
+    /// default, since determining this requires invoking `git`.
+    pub fn include_dirty(mut self, include: bool) -> Self {
+        self.include_dirty = include;
+        self
+    }
+
+    /// Parses `version` as a semver version and mangles it into a legal C
+    /// identifier suffix according to this scheme.
+    pub fn mangle(&self, version: &str) -> Result<String, Error> {
+        use std::fmt::Write;
+
+        let version = semver::Version::parse(version)?;
+
+        let mut mangled = String::new();
+        write!(mangled, "v{}_{}_{}", version.major, version.minor, version.patch)?;
+
+        if self.include_pre && !version.pre.is_empty() {
+            write!(mangled, "_{}", sanitize(version.pre.as_str()))?;
+        }
+
+        if self.include_build && !version.build.is_empty() {
+            write!(mangled, "_{}", sanitize(version.build.as_str()))?;
+        }
+
+        Ok(mangled)
+    }
+}
+
+/// Maps every character outside `[A-Za-z0-9_]` to `_` and ensures the result
+/// doesn't begin with a digit, guaranteeing a legal C identifier.
+fn sanitize(s: &str) -> String {
+    let mut out: String = s.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' { c } else { '_' })
+        .collect();
+
+    if out.starts_with(|c: char| c.is_ascii_digit()) {
+        out.insert(0, '_');
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod version_scheme_tests {
+    use super::{sanitize, VersionScheme};
+
+    #[test]
+    fn sanitize_replaces_illegal_characters() {
+        assert_eq!(sanitize("beta.1"), "beta_1");
+        assert_eq!(sanitize("rc-2+build.3"), "rc_2_build_3");
+        assert_eq!(sanitize("already_legal"), "already_legal");
+    }
+
+    #[test]
+    fn sanitize_escapes_a_leading_digit() {
+        assert_eq!(sanitize("1beta"), "_1beta");
+        assert_eq!(sanitize("123"), "_123");
+    }
+
+    #[test]
+    fn default_scheme_includes_pre_and_omits_build() {
+        let scheme = VersionScheme::default();
+        assert_eq!(scheme.mangle("1.2.3").unwrap(), "v1_2_3");
+        assert_eq!(scheme.mangle("1.2.3-beta.1").unwrap(), "v1_2_3_beta_1");
+        assert_eq!(scheme.mangle("1.2.3-beta.1+build.9").unwrap(), "v1_2_3_beta_1");
+    }
+
+    #[test]
+    fn scheme_can_include_build_and_exclude_pre() {
+        let scheme = VersionScheme::new().include_pre(false).include_build(true);
+        assert_eq!(scheme.mangle("1.2.3-beta.1+build.9").unwrap(), "v1_2_3_build_9");
+    }
+
+    #[test]
+    fn mangle_rejects_non_semver_input() {
+        assert!(VersionScheme::default().mangle("not-a-version").is_err());
+    }
+}
+
+/// Resolves the real git directory for the repository rooted at
+/// `repo_root`. Handles the common case where `.git` is itself the git
+/// directory as well as the worktree/submodule case where `.git` is a file
+/// containing a `gitdir: <path>` pointer to the real one.
+///
+/// For a linked worktree, the returned directory is the worktree-private
+/// one (e.g. `.git/worktrees/<name>`); pass it to [`common_dir`] to find
+/// where refs actually live.
+fn resolve_git_dir(repo_root: &Path) -> io::Result<Option<PathBuf>> {
+    let dot_git = repo_root.join(".git");
+    let metadata = match fs::metadata(&dot_git) {
+        Ok(metadata) => metadata,
+        Err(e) => match e.kind() {
+            io::ErrorKind::NotFound => return Ok(None),
+            _ => return Err(e)
+        }
+    };
+
+    if metadata.is_dir() {
+        return Ok(Some(dot_git));
+    }
+
+    let mut contents = String::new();
+    File::open(&dot_git)?.read_to_string(&mut contents)?;
+
+    let gitdir = contents.trim_start_matches("gitdir:").trim();
+    Ok(Some(repo_root.join(gitdir)))
+}
+
+/// Resolves the directory that holds the repository's refs and
+/// `packed-refs`. In a linked worktree, `git_dir` (as returned by
+/// [`resolve_git_dir`]) is the worktree-private directory, which contains a
+/// `commondir` file pointing back at the main `.git` directory where refs
+/// are actually shared from; everywhere else, `git_dir` already is that
+/// directory.
+fn common_dir(git_dir: &Path) -> io::Result<PathBuf> {
+    match fs::read_to_string(git_dir.join("commondir")) {
+        Ok(contents) => Ok(git_dir.join(contents.trim())),
+        Err(e) => match e.kind() {
+            io::ErrorKind::NotFound => Ok(git_dir.to_path_buf()),
+            _ => Err(e)
+        }
+    }
+}
+
+/// Reads `ref_name` (e.g. `refs/heads/master`) out of `git_dir`, first as a
+/// loose ref file, falling back to a scan of `packed-refs` when the loose
+/// ref doesn't exist, as happens after `git gc` or a shallow clone.
+fn read_ref(git_dir: &Path, ref_name: &str) -> io::Result<Option<String>> {
+    match File::open(git_dir.join(ref_name)) {
+        Ok(mut file) => {
+            let mut contents = String::new();
+            file.read_to_string(&mut contents)?;
+            Ok(Some(contents.trim().to_string()))
+        }
+        Err(e) => match e.kind() {
+            io::ErrorKind::NotFound => read_packed_ref(git_dir, ref_name),
+            _ => Err(e)
+        }
+    }
+}
+
+fn read_packed_ref(git_dir: &Path, ref_name: &str) -> io::Result<Option<String>> {
+    let contents = match fs::File::open(git_dir.join("packed-refs")) {
+        Ok(mut file) => {
+            let mut contents = String::new();
+            file.read_to_string(&mut contents)?;
+            contents
+        }
+        Err(e) => match e.kind() {
+            io::ErrorKind::NotFound => return Ok(None),
+            _ => return Err(e)
+        }
+    };
+
+    for line in contents.lines() {
+        if line.starts_with('#') || line.starts_with('^') {
+            continue;
+        }
+
+        let mut parts = line.splitn(2, ' ');
+        if let (Some(hash), Some(name)) = (parts.next(), parts.next()) {
+            if name == ref_name {
+                return Ok(Some(hash.to_string()));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+fn git_shorthash(repo_root: &Path) -> io::Result<Option<String>> {
+    let git_dir = match resolve_git_dir(repo_root)? {
+        Some(git_dir) => git_dir,
+        None => return Ok(None),
+    };
+
+    let mut contents = String::new();
+    File::open(git_dir.join("HEAD"))?.read_to_string(&mut contents)?;
+
+    let hash = if let Some(ref_name) = contents.strip_prefix("ref: ") {
+        read_ref(&common_dir(&git_dir)?, ref_name.trim())?
+    } else {
+        Some(contents.trim().to_string())
+    };
+
+    match hash {
+        Some(hash) if hash.len() >= 8 => Ok(Some(hash[..8].to_string())),
+        _ => Err(io::Error::new(io::ErrorKind::InvalidData, "invalid git ref")),
+    }
+}
+
+#[cfg(test)]
+mod git_dir_tests {
+    use super::{git_shorthash, resolve_git_dir};
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+
+    /// A fresh, empty scratch directory under the system temp dir, removed
+    /// if a previous run of this test left one behind.
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("native_versioning_test_{}", name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn resolve_git_dir_missing_repository() {
+        let root = scratch_dir("missing");
+        assert!(resolve_git_dir(&root).unwrap().is_none());
+    }
+
+    #[test]
+    fn resolve_git_dir_plain_repository() {
+        let root = scratch_dir("plain");
+        fs::create_dir_all(root.join(".git")).unwrap();
+
+        assert_eq!(resolve_git_dir(&root).unwrap().unwrap(), root.join(".git"));
+    }
+
+    #[test]
+    fn git_shorthash_reads_loose_ref() {
+        let root = scratch_dir("loose");
+        let git_dir = root.join(".git");
+        fs::create_dir_all(git_dir.join("refs/heads")).unwrap();
+        fs::write(git_dir.join("HEAD"), "ref: refs/heads/master\n").unwrap();
+        fs::write(git_dir.join("refs/heads/master"), "abcdef0123456789\n").unwrap();
+
+        assert_eq!(git_shorthash(&root).unwrap().as_deref(), Some("abcdef01"));
+    }
+
+    #[test]
+    fn git_shorthash_falls_back_to_packed_refs() {
+        let root = scratch_dir("packed");
+        let git_dir = root.join(".git");
+        fs::create_dir_all(&git_dir).unwrap();
+        fs::write(git_dir.join("HEAD"), "ref: refs/heads/master\n").unwrap();
+        fs::write(git_dir.join("packed-refs"),
+            "# pack-refs with: peeled fully-peeled sorted\n\
+             1122334455667788990011223344556677889900 refs/heads/master\n").unwrap();
+
+        assert_eq!(git_shorthash(&root).unwrap().as_deref(), Some("11223344"));
+    }
+
+    #[test]
+    fn git_shorthash_follows_worktree_commondir() {
+        let root = scratch_dir("worktree");
+        let common_dir = root.join(".git");
+        fs::create_dir_all(common_dir.join("refs/heads")).unwrap();
+        fs::write(common_dir.join("refs/heads/other"), "deadbeefcafebabe\n").unwrap();
+
+        let worktree_root = root.join("wt");
+        let private_dir = common_dir.join("worktrees/wt");
+        fs::create_dir_all(&private_dir).unwrap();
+        fs::create_dir_all(&worktree_root).unwrap();
+        fs::write(worktree_root.join(".git"),
+            format!("gitdir: {}\n", private_dir.display())).unwrap();
+        fs::write(private_dir.join("HEAD"), "ref: refs/heads/other\n").unwrap();
+        fs::write(private_dir.join("commondir"), "../..\n").unwrap();
+
+        assert_eq!(git_shorthash(&worktree_root).unwrap().as_deref(), Some("deadbeef"));
+    }
+}
+
+/// Best-effort check for uncommitted changes in the working tree. Shells out
+/// to `git status --porcelain`; if `git` isn't on `PATH` or the invocation
+/// otherwise fails, the tree is conservatively treated as clean rather than
+/// failing the build.
+fn git_dirty() -> bool {
+    Command::new("git")
+        .args(["status", "--porcelain"])
+        .output()
+        .map(|output| output.status.success() && !output.stdout.is_empty())
+        .unwrap_or(false)
+}
+
+fn version(scheme: &VersionScheme) -> Result<String, Error> {
     use std::fmt::Write;
 
-    let mut version = String::new();
-    write!(version, "_v{}", env::var("CARGO_PKG_VERSION_MAJOR")?)?;
-    write!(version, "_{}", env::var("CARGO_PKG_VERSION_MINOR")?)?;
-    write!(version, "_{}", env::var("CARGO_PKG_VERSION_PATCH")?)?;
-    write!(version, "_{}", env::var("CARGO_PKG_VERSION_PRE")?)?;
+    let mut version = scheme.mangle(&env::var("CARGO_PKG_VERSION")?)?;
+    if let Some(shorthash) = git_shorthash(Path::new("."))? {
+        write!(version, "_{}", shorthash)?;
+    }
+
+    if scheme.include_dirty && git_dirty() {
+        write!(version, "_dirty")?;
+    }
+
     Ok(version)
 }
 
@@ -75,23 +416,195 @@ impl HeaderInclude for cc::Build {
     }
 }
 
-/// Generates the versioned header file with the version mangling macro.
+/// Trait that provides the [`version_symbols`] method for `cc::Build`,
+/// mirroring [`write_symbol_defines`] but riding along on the `cc` compiler
+/// invocation via `-D` instead of writing a header to disk.
+///
+/// This is an alternative to [`write_symbol_defines`] for users who don't
+/// need an `#include`-able header: no file is written and no manual
+/// `#include`/`include_header` step is required, which also composes more
+/// cleanly with cross-compilation, since the defines ride along with the
+/// `cc::Build` invocation that's already handling the target.
+///
+/// [`version_symbols`]: VersionedBuild::version_symbols
+/// [`write_symbol_defines`]: fn.write_symbol_defines.html
+pub trait VersionedBuild {
+    /// Defines the `VERSIONED(sym)` macro and, for each symbol in `syms`,
+    /// defines `sym` to its mangled name, using the default [`VersionScheme`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the version cannot be determined, e.g. because a required
+    /// `CARGO_PKG_VERSION*` environment variable is unset.
+    fn version_symbols(&mut self, syms: &[&str]) -> &mut Self;
+
+    /// Like [`version_symbols`], but mangles the version according to
+    /// `scheme` instead of the default scheme.
+    ///
+    /// [`version_symbols`]: VersionedBuild::version_symbols
+    ///
+    /// # Panics
+    ///
+    /// Panics if the version cannot be determined, e.g. because a required
+    /// `CARGO_PKG_VERSION*` environment variable is unset.
+    fn version_symbols_with_scheme(&mut self, syms: &[&str], scheme: &VersionScheme) -> &mut Self;
+}
+
+/// Formats the value of the `VERSIONED(sym)` macro define: a token-paste
+/// expression expanding `sym` into its mangled name.
+fn versioned_macro_define(version: &str) -> String {
+    format!("sym ## _{}", version)
+}
+
+/// Formats the `-D` name/value pairs for a single symbol: the mangled define
+/// itself, plus, when `apple` is set, the leading-underscore variant used
+/// when assembly references Apple's C symbols directly.
+fn symbol_defines(sym: &str, version: &str, apple: bool) -> Vec<(String, String)> {
+    let mut defines = vec![(sym.to_string(), format!("{}_{}", sym, version))];
+    if apple {
+        defines.push((format!("_{}", sym), format!("_{}_{}", sym, version)));
+    }
+
+    defines
+}
+
+impl VersionedBuild for cc::Build {
+    fn version_symbols(&mut self, syms: &[&str]) -> &mut Self {
+        self.version_symbols_with_scheme(syms, &VersionScheme::default())
+    }
+
+    fn version_symbols_with_scheme(&mut self, syms: &[&str], scheme: &VersionScheme) -> &mut Self {
+        let version = version(scheme).expect("native_versioning: could not determine version");
+        println!("cargo:rustc-env={}={}", ENV_NAME, version);
+
+        let is_apple = env::var("CARGO_CFG_TARGET_OS")
+            .map(|os| os == "macos" || os == "ios")
+            .unwrap_or(false);
+
+        self.define("VERSIONED(sym)", Some(&*versioned_macro_define(&version)));
+
+        for sym in syms {
+            for (name, value) in symbol_defines(sym, &version, is_apple) {
+                self.define(&name, Some(&*value));
+            }
+        }
+
+        self
+    }
+}
+
+#[cfg(test)]
+mod symbol_define_tests {
+    use super::{symbol_defines, versioned_macro_define};
+
+    #[test]
+    fn versioned_macro_expands_with_the_mangled_version() {
+        assert_eq!(versioned_macro_define("v1_2_3"), "sym ## _v1_2_3");
+    }
+
+    #[test]
+    fn symbol_defines_mangles_the_plain_name() {
+        assert_eq!(symbol_defines("foo", "v1_2_3", false),
+            vec![("foo".to_string(), "foo_v1_2_3".to_string())]);
+    }
+
+    #[test]
+    fn symbol_defines_adds_the_apple_underscore_variant() {
+        assert_eq!(symbol_defines("foo", "v1_2_3", true), vec![
+            ("foo".to_string(), "foo_v1_2_3".to_string()),
+            ("_foo".to_string(), "_foo_v1_2_3".to_string()),
+        ]);
+    }
+}
+
+/// Generates the versioned header file with the version mangling CPP macro
+/// and exports an environment variable with the current project's version,
+/// using the default [`VersionScheme`].
 ///
 /// The header is generated in a file named `header_filename` in the path
-/// `include_dir`. The versioned macro will be named `macro_name`.
+/// `include_dir`. The versioned macro will be named `macro_name`. The
+/// environment variable is exported by printing
+/// `cargo:rustc-env=NATIVE_VERSIONING_VERSION=$value` to `stdout`.
 pub fn write_versioned_header<I, H>(
     include_dir: I,
     header_filename: H,
     macro_name: &str
 ) -> Result<PathBuf, Error>
     where I: AsRef<Path>, H: AsRef<Path>
+{
+    write_versioned_header_with_scheme(
+        include_dir, header_filename, macro_name, &VersionScheme::default())
+}
+
+/// Like [`write_versioned_header`], but mangles the version according to
+/// `scheme` instead of the default scheme.
+pub fn write_versioned_header_with_scheme<I, H>(
+    include_dir: I,
+    header_filename: H,
+    macro_name: &str,
+    scheme: &VersionScheme,
+) -> Result<PathBuf, Error>
+    where I: AsRef<Path>, H: AsRef<Path>
 {
     let include_dir = include_dir.as_ref();
     let versioned_h = include_dir.join(header_filename.as_ref());
+    let version = version(scheme)?;
 
     fs::create_dir_all(include_dir)?;
     let mut file = File::create(&versioned_h)?;
-    write!(file, "#define {}(sym) sym ## {}\n", macro_name, version()?)?;
+    write!(file, "#define {}(sym) sym ## _{}\n", macro_name, version)?;
+    println!("cargo:rustc-env={}={}", ENV_NAME, version);
+
+    Ok(versioned_h)
+}
+
+/// Generates the versioned header file, just like [`write_versioned_header`],
+/// and additionally appends a `#define sym MACRO(sym)` line for every symbol
+/// in `symbols` -- the same lines users previously had to hand-maintain
+/// alongside their `versioned_extern!` block. When the target is an Apple
+/// platform, a `#define _sym MACRO(_sym)` line is emitted as well, for
+/// symbols referenced with a leading underscore from assembly.
+///
+/// Pair this with [`versioned_symbols!`] to derive `symbols` from the same
+/// block passed to `versioned_extern!`, so the two lists can never diverge.
+///
+/// [`versioned_symbols!`]: macro.versioned_symbols.html
+pub fn write_symbol_defines<I, H>(
+    include_dir: I,
+    header_filename: H,
+    macro_name: &str,
+    symbols: &[&str],
+) -> Result<PathBuf, Error>
+    where I: AsRef<Path>, H: AsRef<Path>
+{
+    write_symbol_defines_with_scheme(
+        include_dir, header_filename, macro_name, symbols, &VersionScheme::default())
+}
+
+/// Like [`write_symbol_defines`], but mangles the version according to
+/// `scheme` instead of the default scheme.
+pub fn write_symbol_defines_with_scheme<I, H>(
+    include_dir: I,
+    header_filename: H,
+    macro_name: &str,
+    symbols: &[&str],
+    scheme: &VersionScheme,
+) -> Result<PathBuf, Error>
+    where I: AsRef<Path>, H: AsRef<Path>
+{
+    let versioned_h = write_versioned_header_with_scheme(
+        include_dir, header_filename, macro_name, scheme)?;
+    let is_apple = env::var("CARGO_CFG_TARGET_OS")
+        .map(|os| os == "macos" || os == "ios")
+        .unwrap_or(false);
+
+    let mut file = fs::OpenOptions::new().append(true).open(&versioned_h)?;
+    for symbol in symbols {
+        writeln!(file, "#define {} {}({})", symbol, macro_name, symbol)?;
+        if is_apple {
+            writeln!(file, "#define _{} {}(_{})", symbol, macro_name, symbol)?;
+        }
+    }
 
     Ok(versioned_h)
 }
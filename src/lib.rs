@@ -161,6 +161,22 @@
 //! }
 //! ```
 //!
+//! ### Skipping the Header Entirely
+//!
+//! If you don't need an `#include`-able header -- for instance, because your
+//! C sources reference symbols directly rather than through the `VERSIONED`
+//! macro -- use [`VersionedBuild::version_symbols`] to inject the same
+//! mangling as compiler `-D` defines instead:
+//!
+//! ```rust,ignore
+//! use native_versioning::VersionedBuild;
+//!
+//! cc::Build::new()
+//!     .file(Path::new("ext").join("foo.c"))
+//!     .version_symbols(&["foo", "bar"])
+//!     .compile("foo");
+//! ```
+//!
 //! ## Importing Mangled Symbols
 //!
 //! To import the versioned symbols on the Rust side, use the
@@ -185,8 +201,57 @@
 //! you can take an existing codebase and simply replace all appearances of
 //! `extern {` with `versioned_extern! {`.
 //!
+//! ## Deriving the Custom Header
+//!
+//! Hand-maintaining the `#define foo VERSIONED(foo)` lines above in lockstep
+//! with the `versioned_extern!` block is error-prone. Instead, pair
+//! [`versioned_symbols!`] with [`write_symbol_defines`] to derive both from
+//! one list of symbol names:
+//!
+//! ```rust
+//! #[macro_use] extern crate native_versioning;
+//!
+//! versioned_symbols! {
+//!     SYMBOLS;
+//!     fn foo(u8) -> u8;
+//!     fn bar(*mut i16, *mut i32);
+//! }
+//! # fn main() {}
+//! ```
+//!
+//! ```rust,ignore
+//! write_symbol_defines(&generated_include_dir, GENERATED_VERSIONED_HEADER,
+//!                      GENERATED_VERSIONED_MACRO, SYMBOLS)
+//!     .expect("generated versioned header file");
+//! ```
+//!
+//! ### Prerelease and Build Metadata
+//!
+//! By default, a crate's prerelease component (`1.2.3-beta.1`) is sanitized
+//! into the mangled version and build metadata (`1.2.3+build.3`) is left
+//! out, matching semver's own precedence rules. Pass a [`VersionScheme`] to
+//! the `_with_scheme` variant of any `write_*` function to change this:
+//!
+//! ```rust,ignore
+//! let scheme = VersionScheme::new().include_build(true);
+//! write_versioned_header_with_scheme(&generated_include_dir,
+//!                                    GENERATED_VERSIONED_HEADER,
+//!                                    GENERATED_VERSIONED_MACRO,
+//!                                    &scheme)
+//!     .expect("generated versioned header file");
+//! ```
+//!
+//! The git short hash appended to the mangled version is resolved directly
+//! from `.git`, so it works for regular checkouts, worktrees, and submodules
+//! alike, and falls back to scanning `packed-refs` when the ref isn't a
+//! loose file on disk. Enable [`VersionScheme::include_dirty`] to also
+//! append a `_dirty` marker when the working tree has uncommitted changes.
+//!
 //! [`write_versioned_header()`]: fn.write_versioned_header.html
 //! [`versioned_extern!`]: macro.versioned_extern.html
+//! [`versioned_symbols!`]: macro.versioned_symbols.html
+//! [`write_symbol_defines`]: fn.write_symbol_defines.html
+//! [`VersionScheme`]: struct.VersionScheme.html
 //!
 mod versioned_extern;
 
@@ -1,46 +1,121 @@
 /// Drop in replacement for `extern` blocks. Sets the `link_name` of every
 /// symbol to the mangled version.
+///
+/// Besides plain C-ABI `extern { ... }` blocks, this macro also accepts:
+///
+///   - An explicit ABI, as the first token in the block (after any inner
+///     attributes, see below), just like
+///     `extern "abi" { ... }`:
+///
+///     ```rust,ignore
+///     versioned_extern! {
+///         "system"
+///         fn foo();
+///     }
+///     ```
+///
+///   - Variadic functions (a trailing `...` in the argument list), which are
+///     passed through unchanged:
+///
+///     ```rust,ignore
+///     versioned_extern! {
+///         fn printf(fmt: *const c_char, ...) -> c_int;
+///     }
+///     ```
+///
+///   - Inner attributes (`#![...]`), applied once to the generated `extern`
+///     block -- most commonly `#![link(...)]`. These are distinguished from
+///     the ordinary, per-item outer attributes (`#[...]`) already supported
+///     on each `fn`/`static`, the same way Rust distinguishes the two:
+///
+///     ```rust,ignore
+///     versioned_extern! {
+///         #![link(name = "foo", kind = "static")]
+///         "system"
+///
+///         fn foo();
+///     }
+///     ```
 #[macro_export]
 macro_rules! versioned_extern {
+    // NOTE: keep this macro's item grammar (`fn`/`pub fn`/`static`/`pub
+    // static`) in sync with `versioned_symbols!` below.
+
+    // Leading block-level attributes, optionally followed by an explicit ABI.
+    ($(#![$($battr:tt)*])+ $abi:literal $($rest:tt)*) => (
+        versioned_extern!([$(#[$($battr)*])+ extern $abi] $($rest)*);
+    );
+
+    ($(#![$($battr:tt)*])+ $($rest:tt)*) => (
+        versioned_extern!([$(#[$($battr)*])+ extern] $($rest)*);
+    );
+
+    // A leading ABI with no block-level attributes.
+    ($abi:literal $($rest:tt)*) => (
+        versioned_extern!([extern $abi] $($rest)*);
+    );
+
+    // No block-level attributes, no ABI: anchor on the item keyword exactly
+    // as before, defaulting the extern block to plain `extern`.
     ($(#[$($attr:tt)*])* fn $($rest:tt)+) => (
-        versioned_extern!([$(#[$($attr)*])* fn] $($rest)+);
+        versioned_extern!([extern] [$(#[$($attr)*])* fn] $($rest)+);
     );
 
     ($(#[$($attr:tt)*])* pub fn $($rest:tt)+) => (
-        versioned_extern!([$(#[$($attr)*])* pub fn] $($rest)+);
+        versioned_extern!([extern] [$(#[$($attr)*])* pub fn] $($rest)+);
     );
 
     ($(#[$($attr:tt)*])* static $($rest:tt)+) => (
-        versioned_extern!([$(#[$($attr)*])* static] $($rest)+);
+        versioned_extern!([extern] [$(#[$($attr)*])* static] $($rest)+);
     );
 
     ($(#[$($attr:tt)*])* pub static $($rest:tt)+) => (
-        versioned_extern!([$(#[$($attr)*])* pub static] $($rest)+);
+        versioned_extern!([extern] [$(#[$($attr)*])* pub static] $($rest)+);
+    );
+
+    ([$($extrn:tt)+] $(#[$($attr:tt)*])* fn $($rest:tt)+) => (
+        versioned_extern!([$($extrn)+] [$(#[$($attr)*])* fn] $($rest)+);
+    );
+
+    ([$($extrn:tt)+] $(#[$($attr:tt)*])* pub fn $($rest:tt)+) => (
+        versioned_extern!([$($extrn)+] [$(#[$($attr)*])* pub fn] $($rest)+);
+    );
+
+    ([$($extrn:tt)+] $(#[$($attr:tt)*])* static $($rest:tt)+) => (
+        versioned_extern!([$($extrn)+] [$(#[$($attr)*])* static] $($rest)+);
+    );
+
+    ([$($extrn:tt)+] $(#[$($attr:tt)*])* pub static $($rest:tt)+) => (
+        versioned_extern!([$($extrn)+] [$(#[$($attr)*])* pub static] $($rest)+);
     );
 
-    ([$($pre:tt)+] $name:ident ($($args:tt)*); $($rest:tt)*) => (
+    ([$($extrn:tt)+] [$($pre:tt)+] $name:ident ($($args:tt)*); $($rest:tt)*) => (
         versioned_extern!(
+            [$($extrn)+]
             $($rest)*
             ([$($pre)+] $name [($($args)*);])
         );
     );
 
-    ([$($pre:tt)+] $name:ident ($($args:tt)*) -> $T:ty; $($rest:tt)*) => (
+    ([$($extrn:tt)+] [$($pre:tt)+] $name:ident ($($args:tt)*) -> $T:ty; $($rest:tt)*) => (
         versioned_extern!(
+            [$($extrn)+]
             $($rest)*
             ([$($pre)+] $name [($($args)*) -> $T;])
         );
     );
 
-    ([$($pre:tt)+] $name:ident : $T:path; $($rest:tt)*) => (
+    ([$($extrn:tt)+] [$($pre:tt)+] $name:ident : $T:path; $($rest:tt)*) => (
         versioned_extern!(
+            [$($extrn)+]
             $($rest)*
             ([$($pre)+] $name [: $T;])
         );
     );
 
-    ($(([$($pre:tt)+] $name:ident [$($post:tt)+]))+) => (
+    ([$($extrn:tt)+] $(([$($pre:tt)+] $name:ident [$($post:tt)+]))+) => (
         versioned_extern! {
+            [$($extrn)+]
             $(
                 [concat!(stringify!($name), "_", env!("NATIVE_VERSIONING_VERSION"))]
 
@@ -53,8 +128,8 @@ macro_rules! versioned_extern {
         }
     );
 
-    ($([$v:expr] [$($pre:tt)+] $name:ident [$($post:tt)+])+) => (
-        extern {$(
+    ([$($extrn:tt)+] $([$v:expr] [$($pre:tt)+] $name:ident [$($post:tt)+])+) => (
+        $($extrn)+ {$(
             #[link_name = $v]
             $($pre)+ $name $($post)+
         )+}
@@ -62,3 +137,79 @@ macro_rules! versioned_extern {
 
     ($($rest:tt)*) => ($($rest)*);
 }
+
+/// Companion to [`versioned_extern!`] that records the symbol names declared
+/// in an identically-shaped block as a `const` slice, instead of rewriting
+/// them into an `extern` block.
+///
+/// Pass it the same `fn`/`static` declarations you'd pass to
+/// `versioned_extern!`, led by the name of the `const` to generate. Any
+/// leading ABI literal or `#![...]` block attributes `versioned_extern!`
+/// accepts are also accepted here -- and ignored, since they don't affect
+/// the symbol list -- so the exact same block can be fed to both macros:
+///
+/// ```rust
+/// # #[macro_use] extern crate native_versioning;
+/// versioned_symbols! {
+///     SYMBOLS;
+///     fn foo(u8) -> u8;
+///     fn bar(*mut i16, *mut i32);
+/// }
+/// # fn main() {}
+/// ```
+///
+/// expands to `pub const SYMBOLS: &'static [&'static str] = &["foo", "bar"];`.
+/// Keeping `versioned_symbols!` in sync with the `versioned_extern!` block it
+/// mirrors means [`write_symbol_defines`] can generate the C-side
+/// `#define foo VERSIONED(foo)` lines straight from this list, so the
+/// Rust-side and C-side symbol lists can never drift apart.
+///
+/// [`write_symbol_defines`]: fn.write_symbol_defines.html
+#[macro_export]
+macro_rules! versioned_symbols {
+    ($name:ident; $($rest:tt)*) => (
+        versioned_symbols!(@collect $name [] $($rest)*);
+    );
+
+    // Leading block-level attributes and/or an explicit ABI, as accepted by
+    // `versioned_extern!`, are irrelevant to the symbol list -- skip them.
+    (@collect $name:ident [$($acc:expr),*] $(#![$($battr:tt)*])+ $abi:literal $($rest:tt)*) => (
+        versioned_symbols!(@collect $name [$($acc),*] $($rest)*);
+    );
+
+    (@collect $name:ident [$($acc:expr),*] $(#![$($battr:tt)*])+ $($rest:tt)*) => (
+        versioned_symbols!(@collect $name [$($acc),*] $($rest)*);
+    );
+
+    (@collect $name:ident [$($acc:expr),*] $abi:literal $($rest:tt)*) => (
+        versioned_symbols!(@collect $name [$($acc),*] $($rest)*);
+    );
+
+    (@collect $name:ident [$($acc:expr),*] $(#[$($attr:tt)*])* fn $sym:ident ($($args:tt)*); $($rest:tt)*) => (
+        versioned_symbols!(@collect $name [$($acc,)* stringify!($sym)] $($rest)*);
+    );
+
+    (@collect $name:ident [$($acc:expr),*] $(#[$($attr:tt)*])* fn $sym:ident ($($args:tt)*) -> $T:ty; $($rest:tt)*) => (
+        versioned_symbols!(@collect $name [$($acc,)* stringify!($sym)] $($rest)*);
+    );
+
+    (@collect $name:ident [$($acc:expr),*] $(#[$($attr:tt)*])* pub fn $sym:ident ($($args:tt)*); $($rest:tt)*) => (
+        versioned_symbols!(@collect $name [$($acc,)* stringify!($sym)] $($rest)*);
+    );
+
+    (@collect $name:ident [$($acc:expr),*] $(#[$($attr:tt)*])* pub fn $sym:ident ($($args:tt)*) -> $T:ty; $($rest:tt)*) => (
+        versioned_symbols!(@collect $name [$($acc,)* stringify!($sym)] $($rest)*);
+    );
+
+    (@collect $name:ident [$($acc:expr),*] $(#[$($attr:tt)*])* static $sym:ident : $T:path; $($rest:tt)*) => (
+        versioned_symbols!(@collect $name [$($acc,)* stringify!($sym)] $($rest)*);
+    );
+
+    (@collect $name:ident [$($acc:expr),*] $(#[$($attr:tt)*])* pub static $sym:ident : $T:path; $($rest:tt)*) => (
+        versioned_symbols!(@collect $name [$($acc,)* stringify!($sym)] $($rest)*);
+    );
+
+    (@collect $name:ident [$($acc:expr),*]) => (
+        pub const $name: &'static [&'static str] = &[$($acc),*];
+    );
+}
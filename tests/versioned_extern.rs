@@ -1,12 +1,10 @@
-#![feature(trace_macros)]
-
-trace_macros!(true);
-
 #[macro_use]
 extern crate native_versioning;
 
 mod c {
     pub type long = u16;
+    pub type char = u8;
+    pub type int = i32;
 }
 
 versioned_extern! {
@@ -21,4 +19,18 @@ versioned_extern! {
     pub fn g();
 }
 
+versioned_extern! {
+    "system"
+
+    pub fn h(x: c::int);
+}
+
+// `libc` is already linked into every Rust binary, so this both exercises
+// the `#![link(...)]` passthrough and actually links.
+versioned_extern! {
+    #![link(name = "c")]
+
+    pub fn printf(fmt: *const c::char, ...) -> c::int;
+}
+
 pub fn main() { }
@@ -0,0 +1,39 @@
+#[macro_use]
+extern crate native_versioning;
+
+mod c {
+    pub type long = u16;
+    pub type char = u8;
+    pub type int = i32;
+}
+
+versioned_symbols! {
+    SYMBOLS;
+
+    static demo: c::long;
+
+    pub static demo2: usize;
+
+    #[cfg(test)]
+    #[doc = "hi"]
+    fn f() -> usize;
+
+    pub fn g();
+}
+
+// The exact block passed to `versioned_extern!` for a variadic,
+// `#![link(...)]` extern block is also accepted here, so the two macros stay
+// composable.
+versioned_symbols! {
+    LINKED_SYMBOLS;
+
+    #![link(name = "c")]
+
+    pub fn printf(fmt: *const c::char, ...) -> c::int;
+}
+
+#[test]
+fn symbol_lists_match_the_declared_items() {
+    assert_eq!(SYMBOLS, &["demo", "demo2", "f", "g"]);
+    assert_eq!(LINKED_SYMBOLS, &["printf"]);
+}